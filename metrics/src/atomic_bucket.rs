@@ -0,0 +1,211 @@
+// Copyright 2019-2020 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+// Each block rollover briefly takes the exclusive write lock, blocking
+// every other writer until it completes, so capacity is sized to keep
+// rollovers rare (once per 65,536 slots claimed) rather than a per-write
+// cost.
+const BLOCK_CAPACITY: usize = 65536;
+
+struct Block {
+    next: Option<Arc<Block>>,
+    index: AtomicUsize,
+    slots: Box<[AtomicU64; BLOCK_CAPACITY]>,
+}
+
+impl Block {
+    fn new(next: Option<Arc<Block>>) -> Arc<Self> {
+        Arc::new(Self {
+            next,
+            index: AtomicUsize::new(0),
+            slots: Box::new(std::array::from_fn(|_| AtomicU64::new(0))),
+        })
+    }
+}
+
+/// A multi-producer bucket for recording `u64` values at very high write
+/// rates, with snapshots that never block in-flight writers.
+///
+/// Writers append to the active block by claiming a range of free slots
+/// with a single `fetch_add` on the block's index; once a block fills, a
+/// fresh block is linked in ahead of it. The active block is held behind
+/// an `RwLock<Arc<Block>>`: writers take only a shared read lock for the
+/// full claim-and-store, so concurrently writing threads never block each
+/// other *within* a block, while `snapshot()` takes the exclusive write
+/// lock to swap the chain out for a fresh, empty block. Because the write
+/// lock can only be acquired once every writer holding a read lock on the
+/// old block has finished storing its value, a snapshot never observes a
+/// claimed slot that hasn't been written yet, and `Arc`'s refcounting
+/// means it can never free a block a writer still references.
+///
+/// Rolling over to a new block also takes that same exclusive write lock,
+/// so writers do briefly contend with each other on every
+/// `BLOCK_CAPACITY`th write, not just on `snapshot()`/`clear()`. At tens
+/// of millions of writes per second per core, `BLOCK_CAPACITY` is sized
+/// so rollovers stay rare relative to the write rate rather than claiming
+/// this type never blocks a writer at all.
+pub struct AtomicBucket {
+    head: RwLock<Arc<Block>>,
+}
+
+impl AtomicBucket {
+    /// Creates an empty bucket.
+    pub fn new() -> Self {
+        Self {
+            head: RwLock::new(Block::new(None)),
+        }
+    }
+
+    /// Records a single occurrence of `value`.
+    pub fn add(&self, value: u64) {
+        self.add_n(value, 1);
+    }
+
+    /// Records `count` occurrences of `value`, claiming a contiguous
+    /// range of slots with a single `fetch_add` per block rather than
+    /// looping one claim at a time.
+    pub fn add_n(&self, value: u64, count: u64) {
+        let mut remaining = count as usize;
+        while remaining > 0 {
+            let guard = self.head.read().unwrap();
+            let head = guard.clone();
+            let start = head.index.fetch_add(remaining, Ordering::Relaxed);
+            if start >= BLOCK_CAPACITY {
+                drop(guard);
+                self.roll_block(&head);
+                continue;
+            }
+            let filled = (BLOCK_CAPACITY - start).min(remaining);
+            for offset in 0..filled {
+                head.slots[start + offset].store(value, Ordering::Relaxed);
+            }
+            remaining -= filled;
+            let needs_roll = start + filled >= BLOCK_CAPACITY;
+            // release the read lock before the store above is visible to a
+            // snapshot's write-lock acquisition
+            drop(guard);
+            if needs_roll {
+                self.roll_block(&head);
+            }
+        }
+    }
+
+    /// Installs a fresh block ahead of `expected`, unless another writer
+    /// (or a concurrent snapshot) has already replaced the head.
+    fn roll_block(&self, expected: &Arc<Block>) {
+        let mut guard = self.head.write().unwrap();
+        if Arc::ptr_eq(&guard, expected) {
+            *guard = Block::new(Some(expected.clone()));
+        }
+    }
+
+    /// Atomically swaps out the active chain of blocks for a fresh, empty
+    /// one and drains every recorded value into a `Vec`, oldest first.
+    /// Writers racing with a snapshot either finish against the old chain
+    /// before the swap or land in the freshly installed block afterward.
+    pub fn snapshot(&self) -> Vec<u64> {
+        let old_head = {
+            let mut guard = self.head.write().unwrap();
+            std::mem::replace(&mut *guard, Block::new(None))
+        };
+
+        let mut blocks = Vec::new();
+        let mut current = Some(old_head);
+        while let Some(block) = current {
+            current = block.next.clone();
+            blocks.push(block);
+        }
+        // blocks were collected newest-first; reverse for oldest-to-newest
+        blocks.reverse();
+
+        let mut values = Vec::new();
+        for block in blocks {
+            let claimed = block.index.load(Ordering::Acquire).min(BLOCK_CAPACITY);
+            for slot in &block.slots[..claimed] {
+                values.push(slot.load(Ordering::Relaxed));
+            }
+        }
+        values
+    }
+
+    /// Discards all recorded values without allocating a `Vec` for them.
+    pub fn clear(&self) {
+        *self.head.write().unwrap() = Block::new(None);
+    }
+}
+
+impl Default for AtomicBucket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::thread;
+
+    #[test]
+    fn add_n_rolls_over_multiple_blocks() {
+        let bucket = AtomicBucket::new();
+        let count = (BLOCK_CAPACITY * 3 + 7) as u64;
+        bucket.add_n(1, count);
+        assert_eq!(bucket.snapshot().len(), count as usize);
+    }
+
+    #[test]
+    fn snapshot_drains_and_clears() {
+        let bucket = AtomicBucket::new();
+        bucket.add(1);
+        bucket.add(2);
+        assert_eq!(bucket.snapshot(), vec![1, 2]);
+        assert!(bucket.snapshot().is_empty());
+    }
+
+    /// Regression test for the write/snapshot races found in review: many
+    /// writer threads add concurrently with threads repeatedly
+    /// snapshotting, and every value added must show up in exactly one
+    /// snapshot, with none lost or duplicated.
+    #[test]
+    fn concurrent_add_and_snapshot_loses_no_values() {
+        const WRITERS: usize = 8;
+        const PER_WRITER: usize = 5_000;
+
+        let bucket = Arc::new(AtomicBucket::new());
+        let barrier = Arc::new(Barrier::new(WRITERS + 1));
+        let mut drained = Vec::new();
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|_| {
+                let bucket = bucket.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..PER_WRITER {
+                        bucket.add(1);
+                    }
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        loop {
+            drained.extend(bucket.snapshot());
+            if writers.iter().all(|w| w.is_finished()) {
+                drained.extend(bucket.snapshot());
+                break;
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(drained.len(), WRITERS * PER_WRITER);
+        assert!(drained.iter().all(|&value| value == 1));
+    }
+}
@@ -57,6 +57,8 @@ where
     source: Source,
     reading: AtomicU64,
     histogram: Option<Histogram<T>>,
+    sketch: Option<Sketch>,
+    bucket: Option<AtomicBucket>,
     last_write: AtomicU64,
     latched: bool,
     max: Point,
@@ -103,6 +105,51 @@ where
             source: statistic.source(),
             reading: AtomicU64::default(),
             histogram,
+            sketch: None,
+            bucket: None,
+            last_write: AtomicU64::new(time::precise_time_ns()),
+            latched: true,
+            max: Point::new(0, 0),
+            min: Point::new(0, 0),
+            outputs: Arc::new(Mutex::new(HashSet::new())),
+            has_data: AtomicBool::new(false),
+        }
+    }
+
+    /// Create a new channel backed by a relative-error `Sketch` instead of
+    /// a fixed-precision `Histogram`, guaranteeing a relative error of
+    /// `alpha` on any quantile regardless of the magnitude of recorded
+    /// values.
+    pub fn with_sketch(statistic: &dyn Statistic, alpha: f64) -> Self {
+        Self {
+            statistic: ChannelStatistic::new(statistic),
+            source: statistic.source(),
+            reading: AtomicU64::default(),
+            histogram: None,
+            sketch: Some(Sketch::new(alpha)),
+            bucket: None,
+            last_write: AtomicU64::new(time::precise_time_ns()),
+            latched: true,
+            max: Point::new(0, 0),
+            min: Point::new(0, 0),
+            outputs: Arc::new(Mutex::new(HashSet::new())),
+            has_data: AtomicBool::new(false),
+        }
+    }
+
+    /// Create a new channel whose distribution/time-interval writes are
+    /// absorbed by a low-contention `AtomicBucket` instead of a shared
+    /// `Histogram`, with quantiles served from an `alpha`-relative-error
+    /// `Sketch` that is refreshed by periodically draining the bucket with
+    /// [`Channel::refresh_sketch`].
+    pub fn with_atomic_bucket(statistic: &dyn Statistic, alpha: f64) -> Self {
+        Self {
+            statistic: ChannelStatistic::new(statistic),
+            source: statistic.source(),
+            reading: AtomicU64::default(),
+            histogram: None,
+            sketch: Some(Sketch::new(alpha)),
+            bucket: Some(AtomicBucket::new()),
             last_write: AtomicU64::new(time::precise_time_ns()),
             latched: true,
             max: Point::new(0, 0),
@@ -127,6 +174,9 @@ where
                 if let Some(ref histogram) = self.histogram {
                     histogram.increment(rate, <T as Atomic>::Primitive::from(1_u8));
                 }
+                if let Some(ref sketch) = self.sketch {
+                    sketch.increment(rate, 1);
+                }
                 // track the point of max rate
                 if self.max.time() > 0 {
                     if rate > self.max.value() {
@@ -165,6 +215,9 @@ where
                 if let Some(ref histogram) = self.histogram {
                     histogram.increment(rate, <T as Atomic>::Primitive::from(1_u8));
                 }
+                if let Some(ref sketch) = self.sketch {
+                    sketch.increment(rate, 1);
+                }
                 // track the point of max rate
                 if self.max.time() > 0 {
                     if rate > self.max.value() {
@@ -195,13 +248,35 @@ where
     pub fn record_distribution(&self, time: u64, value: u64, count: <T as Atomic>::Primitive) {
         if self.source == Source::Distribution {
             self.reading.fetch_add(u64::from(count), Ordering::Relaxed);
-            if let Some(ref histogram) = self.histogram {
-                histogram.increment(value, count);
+            if let Some(ref bucket) = self.bucket {
+                // the bucket absorbs the raw values; `refresh_sketch` drains
+                // it into the quantile sketch, so we don't double-count here
+                bucket.add_n(value, u64::from(count));
+            } else {
+                if let Some(ref histogram) = self.histogram {
+                    histogram.increment(value, count);
+                }
+                if let Some(ref sketch) = self.sketch {
+                    sketch.increment(value, u64::from(count));
+                }
             }
             self.last_write.store(time, Ordering::Relaxed);
         }
     }
 
+    /// Drains any `AtomicBucket` configured for this channel into its
+    /// `Sketch`, so that a subsequent `percentile()` call reflects values
+    /// recorded since the last refresh. A no-op if the channel has no
+    /// bucket. Intended to be called periodically, e.g. once per latch
+    /// cycle, rather than on every write.
+    pub fn refresh_sketch(&self) {
+        if let (Some(ref bucket), Some(ref sketch)) = (&self.bucket, &self.sketch) {
+            for value in bucket.snapshot() {
+                sketch.increment(value, 1);
+            }
+        }
+    }
+
     // for Gauge measurements:
     // reading tracks latest reading
     // histogram tracks readings
@@ -213,6 +288,9 @@ where
             if let Some(ref histogram) = self.histogram {
                 histogram.increment(value, <T as Atomic>::Primitive::from(1_u8));
             }
+            if let Some(ref sketch) = self.sketch {
+                sketch.increment(value, 1);
+            }
             // track the point of max gauge reading
             if self.max.time() > 0 {
                 if value > self.max.value() {
@@ -242,6 +320,9 @@ where
             if let Some(ref histogram) = self.histogram {
                 histogram.increment(u64::from(count), <T as Atomic>::Primitive::from(1_u8));
             }
+            if let Some(ref sketch) = self.sketch {
+                sketch.increment(u64::from(count), 1);
+            }
             self.last_write.store(time, Ordering::Relaxed);
         }
     }
@@ -252,8 +333,17 @@ where
         if self.source == Source::TimeInterval {
             self.reading.fetch_add(1, Ordering::Relaxed);
             let duration = stop.wrapping_sub(start);
-            if let Some(ref histogram) = self.histogram {
-                histogram.increment(duration, <T as Atomic>::Primitive::from(1_u8));
+            if let Some(ref bucket) = self.bucket {
+                // the bucket absorbs the raw durations; `refresh_sketch` drains
+                // it into the quantile sketch, so we don't double-count here
+                bucket.add(duration);
+            } else {
+                if let Some(ref histogram) = self.histogram {
+                    histogram.increment(duration, <T as Atomic>::Primitive::from(1_u8));
+                }
+                if let Some(ref sketch) = self.sketch {
+                    sketch.increment(duration, 1);
+                }
             }
             // track point of largest interval
             if self.max.time() > 0 {
@@ -279,10 +369,41 @@ where
         self.reading.load(Ordering::Relaxed)
     }
 
-    /// Calculate a percentile from the histogram, returns `None` if there is no
-    /// histogram for the `Channel`
+    /// Get the `ChannelStatistic` describing this channel's name,
+    /// description, and unit.
+    pub fn statistic(&self) -> &ChannelStatistic {
+        &self.statistic
+    }
+
+    /// Get the `Source` kind of this `Channel`.
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    /// Get the time, in nanoseconds, at which this `Channel` was last
+    /// written to.
+    pub fn last_write(&self) -> u64 {
+        self.last_write.load(Ordering::Relaxed)
+    }
+
+    /// Returns a snapshot of the channel's `Sketch` bucket counts, if one
+    /// is configured, along with the `gamma` needed to interpret it.
+    /// Intended for taking a point-in-time capture of the quantile
+    /// distribution, e.g. for `snapshot::Snapshot::capture`.
+    pub fn sketch_buckets(&self) -> Option<(f64, HashMap<i64, u64>)> {
+        self.sketch.as_ref().map(|sketch| (sketch.gamma(), sketch.buckets()))
+    }
+
+    /// Calculate a percentile from the channel's `Summary` backend. Prefers
+    /// the relative-error `Sketch` when one is configured, otherwise falls
+    /// back to the fixed-precision `Histogram`. Returns an error if the
+    /// `Channel` has neither.
     pub fn percentile(&self, percentile: f64) -> Result<u64, MetricsError> {
-        if let Some(ref histogram) = self.histogram {
+        if let Some(ref sketch) = self.sketch {
+            sketch
+                .percentile(percentile / 100.0)
+                .ok_or(MetricsError::EmptyChannel)
+        } else if let Some(ref histogram) = self.histogram {
             histogram
                 .percentile(percentile)
                 .map_err(|_| MetricsError::EmptyChannel)
@@ -311,6 +432,9 @@ where
             if let Some(ref histogram) = self.histogram {
                 histogram.clear();
             }
+            if let Some(ref sketch) = self.sketch {
+                sketch.clear();
+            }
         }
         self.max.set(0, 0);
         self.min.set(0, 0);
@@ -325,6 +449,12 @@ where
         if let Some(ref histogram) = self.histogram {
             histogram.clear();
         }
+        if let Some(ref sketch) = self.sketch {
+            sketch.clear();
+        }
+        if let Some(ref bucket) = self.bucket {
+            bucket.clear();
+        }
         self.max.set(0, 0);
         self.min.set(0, 0);
     }
@@ -404,3 +534,76 @@ where
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustcommon_atomics::AtomicU64;
+
+    struct TestStatistic {
+        name: &'static str,
+        source: Source,
+    }
+
+    impl Statistic for TestStatistic {
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn source(&self) -> Source {
+            self.source
+        }
+        fn description(&self) -> Option<&str> {
+            None
+        }
+        fn unit(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    #[test]
+    fn with_sketch_reports_a_percentile() {
+        let statistic = TestStatistic {
+            name: "latency",
+            source: Source::Distribution,
+        };
+        let channel = Channel::<AtomicU64>::with_sketch(&statistic, 0.01);
+        for value in 1..=1000u64 {
+            channel.record_distribution(0, value, <AtomicU64 as Atomic>::Primitive::from(1_u8));
+        }
+        let p50 = channel.percentile(50.0).unwrap();
+        assert!((p50 as f64 - 500.0).abs() / 500.0 < 0.01);
+    }
+
+    #[test]
+    fn with_atomic_bucket_absorbs_distribution_writes() {
+        let statistic = TestStatistic {
+            name: "latency",
+            source: Source::Distribution,
+        };
+        let channel = Channel::<AtomicU64>::with_atomic_bucket(&statistic, 0.01);
+        for value in 1..=1000u64 {
+            channel.record_distribution(0, value, <AtomicU64 as Atomic>::Primitive::from(1_u8));
+        }
+        // the bucket absorbs the writes; nothing is visible from the sketch
+        // until a refresh drains the bucket into it
+        assert!(channel.percentile(50.0).is_err());
+        channel.refresh_sketch();
+        let p50 = channel.percentile(50.0).unwrap();
+        assert!((p50 as f64 - 500.0).abs() / 500.0 < 0.01);
+    }
+
+    #[test]
+    fn with_atomic_bucket_absorbs_time_interval_writes() {
+        let statistic = TestStatistic {
+            name: "request_duration",
+            source: Source::TimeInterval,
+        };
+        let channel = Channel::<AtomicU64>::with_atomic_bucket(&statistic, 0.01);
+        channel.record_time_interval(0, 100);
+        channel.record_time_interval(0, 200);
+
+        assert!(channel.percentile(50.0).is_err());
+        channel.refresh_sketch();
+        assert!(channel.percentile(50.0).is_ok());
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2019-2020 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::*;
+
+/// Appends the Prometheus / OpenMetrics text exposition for a single
+/// `Channel` to `buf`.
+///
+/// A `# HELP` line is emitted when the channel has a `description()`, a
+/// `# UNIT` line is emitted when it has a `unit()`, and a `# TYPE` line is
+/// always emitted, using `counter` for `Source::Counter` channels and
+/// `gauge` for everything else. Each `Output::Reading` is rendered as the
+/// bare metric and each `Output::Percentile(p)` is rendered as a sample
+/// with a `percentile` label, so that a scrape endpoint can expose
+/// per-channel percentiles directly.
+pub fn write_channel<T>(buf: &mut String, channel: &Channel<T>)
+where
+    T: Unsigned + SaturatingArithmetic + Default + FetchCompareStore,
+    <T as Atomic>::Primitive: Default + PartialEq + Copy + From<u8>,
+    u64: From<<T as Atomic>::Primitive>,
+{
+    let statistic = channel.statistic();
+    let name = statistic.name();
+
+    if let Some(description) = statistic.description() {
+        buf.push_str(&format!("# HELP {} {}\n", name, description));
+    }
+    if let Some(unit) = statistic.unit() {
+        buf.push_str(&format!("# UNIT {} {}\n", name, unit));
+    }
+    let metric_type = match statistic.source() {
+        Source::Counter => "counter",
+        _ => "gauge",
+    };
+    buf.push_str(&format!("# TYPE {} {}\n", name, metric_type));
+
+    for reading in channel.readings() {
+        match reading.output() {
+            Output::Reading => {
+                buf.push_str(&format!("{} {}\n", name, reading.value()));
+            }
+            Output::Percentile(percentile) => {
+                buf.push_str(&format!(
+                    "{}{{percentile=\"{}\"}} {}\n",
+                    name,
+                    percentile.as_f64(),
+                    reading.value()
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Formats a collection of `Channel`s as a single Prometheus / OpenMetrics
+/// exposition document, suitable for serving directly from a scrape
+/// endpoint.
+pub fn write_channels<'a, T, I>(channels: I) -> String
+where
+    T: Unsigned + SaturatingArithmetic + Default + FetchCompareStore + 'static,
+    <T as Atomic>::Primitive: Default + PartialEq + Copy + From<u8>,
+    u64: From<<T as Atomic>::Primitive>,
+    I: IntoIterator<Item = &'a Channel<T>>,
+{
+    let mut buf = String::new();
+    for channel in channels {
+        write_channel(&mut buf, channel);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustcommon_atomics::AtomicU64;
+
+    struct TestStatistic {
+        name: &'static str,
+        description: Option<&'static str>,
+        unit: Option<&'static str>,
+        source: Source,
+    }
+
+    impl Statistic for TestStatistic {
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn source(&self) -> Source {
+            self.source
+        }
+        fn description(&self) -> Option<&str> {
+            self.description
+        }
+        fn unit(&self) -> Option<&str> {
+            self.unit
+        }
+    }
+
+    #[test]
+    fn writes_help_type_and_reading_lines() {
+        let statistic = TestStatistic {
+            name: "connections",
+            description: Some("active connections"),
+            unit: None,
+            source: Source::Gauge,
+        };
+        let channel = Channel::<AtomicU64>::new(&statistic, None);
+        channel.add_output(Output::Reading);
+        channel.record_gauge(0, 42);
+
+        let mut buf = String::new();
+        write_channel(&mut buf, &channel);
+
+        assert_eq!(
+            buf,
+            "# HELP connections active connections\n# TYPE connections gauge\nconnections 42\n"
+        );
+    }
+
+    #[test]
+    fn omits_help_line_without_a_description() {
+        let statistic = TestStatistic {
+            name: "requests",
+            description: None,
+            unit: None,
+            source: Source::Counter,
+        };
+        let channel = Channel::<AtomicU64>::new(&statistic, None);
+        channel.add_output(Output::Reading);
+        channel.record_counter(0, 0);
+
+        let mut buf = String::new();
+        write_channel(&mut buf, &channel);
+
+        assert!(!buf.contains("# HELP"));
+        assert!(buf.contains("# TYPE requests counter\n"));
+    }
+
+    #[test]
+    fn writes_unit_line_when_present() {
+        let statistic = TestStatistic {
+            name: "latency",
+            description: None,
+            unit: Some("seconds"),
+            source: Source::Gauge,
+        };
+        let channel = Channel::<AtomicU64>::new(&statistic, None);
+        channel.add_output(Output::Reading);
+        channel.record_gauge(0, 1);
+
+        let mut buf = String::new();
+        write_channel(&mut buf, &channel);
+
+        assert_eq!(
+            buf,
+            "# UNIT latency seconds\n# TYPE latency gauge\nlatency 1\n"
+        );
+    }
+
+    #[test]
+    fn omits_unit_line_without_a_unit() {
+        let statistic = TestStatistic {
+            name: "requests",
+            description: None,
+            unit: None,
+            source: Source::Counter,
+        };
+        let channel = Channel::<AtomicU64>::new(&statistic, None);
+        channel.add_output(Output::Reading);
+        channel.record_counter(0, 0);
+
+        let mut buf = String::new();
+        write_channel(&mut buf, &channel);
+
+        assert!(!buf.contains("# UNIT"));
+    }
+}
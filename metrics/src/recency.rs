@@ -0,0 +1,216 @@
+// Copyright 2019-2020 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::*;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// A bitmask over `Source` kinds, used to select which kinds of `Channel`
+/// are eligible for idle expiry.
+///
+/// Mirrors the kind-mask idea from metrics-util: a registry might want to
+/// expire short-lived per-connection `Gauge` or `Distribution` channels
+/// while never expiring long-lived `Counter`s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KindMask(u8);
+
+impl KindMask {
+    const COUNTER: u8 = 0b0001;
+    const GAUGE: u8 = 0b0010;
+    const DISTRIBUTION: u8 = 0b0100;
+    const TIME_INTERVAL: u8 = 0b1000;
+
+    /// A mask that selects no kinds.
+    pub fn none() -> Self {
+        KindMask(0)
+    }
+
+    /// A mask that selects every kind.
+    pub fn all() -> Self {
+        KindMask(Self::COUNTER | Self::GAUGE | Self::DISTRIBUTION | Self::TIME_INTERVAL)
+    }
+
+    /// Returns a copy of this mask with `source` added to the selection.
+    pub fn with(mut self, source: Source) -> Self {
+        self.0 |= Self::bit(source);
+        self
+    }
+
+    /// Returns `true` if `source` is selected by this mask.
+    pub fn contains(self, source: Source) -> bool {
+        self.0 & Self::bit(source) != 0
+    }
+
+    fn bit(source: Source) -> u8 {
+        match source {
+            Source::Counter => Self::COUNTER,
+            Source::Gauge => Self::GAUGE,
+            Source::Distribution => Self::DISTRIBUTION,
+            Source::TimeInterval => Self::TIME_INTERVAL,
+        }
+    }
+}
+
+struct Entry {
+    last_write: u64,
+}
+
+/// Tracks channel recency for a registry and evicts (zeroes) channels that
+/// have gone idle.
+///
+/// A channel is evicted on a `sweep` when its `Source` kind is selected by
+/// the configured `KindMask`, its `last_write` is unchanged since the
+/// tracker last observed it, and that unchanged `last_write` is older than
+/// the configured idle timeout (i.e. it was already idle on the prior pass
+/// too, so a single late write just inside the timeout doesn't cause
+/// eviction).
+pub struct RecencyTracker {
+    timeout_ns: u64,
+    mask: KindMask,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl RecencyTracker {
+    /// Creates a tracker that expires channels selected by `mask` once
+    /// they have gone `timeout_ns` nanoseconds without a write.
+    pub fn new(timeout_ns: u64, mask: KindMask) -> Self {
+        Self {
+            timeout_ns,
+            mask,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs one eviction pass over `channels` at time `now` (nanoseconds),
+    /// zeroing any channel that has been idle for at least one full
+    /// timeout period across two consecutive sweeps.
+    ///
+    /// Also prunes any tracked entry whose channel is no longer present in
+    /// `channels`, so that a registry that drops channels doesn't leave
+    /// this tracker's own `entries` map growing without bound.
+    pub fn sweep<T>(&self, now: u64, channels: &[&Channel<T>])
+    where
+        T: Unsigned + SaturatingArithmetic + Default + FetchCompareStore,
+        <T as Atomic>::Primitive: Default + PartialEq + Copy + From<u8>,
+        u64: From<<T as Atomic>::Primitive>,
+    {
+        let mut entries = self.entries.lock().unwrap();
+        let mut seen = HashSet::with_capacity(channels.len());
+        for channel in channels {
+            if !self.mask.contains(channel.source()) {
+                continue;
+            }
+            let name = channel.statistic().name().to_string();
+            seen.insert(name.clone());
+            let last_write = channel.last_write();
+            match entries.get_mut(&name) {
+                Some(entry) if entry.last_write != last_write => {
+                    entry.last_write = last_write;
+                    continue;
+                }
+                Some(_) => {}
+                None => {
+                    // first observation: no prior sweep to compare against
+                    entries.insert(name, Entry { last_write });
+                    continue;
+                }
+            }
+            if now.saturating_sub(last_write) >= self.timeout_ns {
+                channel.zero();
+            }
+        }
+        entries.retain(|name, _| seen.contains(name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustcommon_atomics::AtomicU64;
+
+    struct TestStatistic {
+        name: &'static str,
+        source: Source,
+    }
+
+    impl Statistic for TestStatistic {
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn source(&self) -> Source {
+            self.source
+        }
+        fn description(&self) -> Option<&str> {
+            None
+        }
+        fn unit(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    #[test]
+    fn zeroes_channel_idle_past_two_consecutive_sweeps() {
+        let statistic = TestStatistic {
+            name: "conn",
+            source: Source::Gauge,
+        };
+        let channel = Channel::<AtomicU64>::new(&statistic, None);
+        channel.record_gauge(0, 7);
+
+        let tracker = RecencyTracker::new(100, KindMask::all());
+        tracker.sweep(50, &[&channel]);
+        assert_eq!(channel.reading(), 7, "not yet past the timeout");
+
+        tracker.sweep(200, &[&channel]);
+        assert_eq!(
+            channel.reading(),
+            0,
+            "channel idle for a full timeout period should be zeroed"
+        );
+    }
+
+    #[test]
+    fn does_not_evict_a_mask_excluded_kind() {
+        let statistic = TestStatistic {
+            name: "conn",
+            source: Source::Counter,
+        };
+        let channel = Channel::<AtomicU64>::new(&statistic, None);
+        channel.record_counter(0, 7);
+
+        let tracker = RecencyTracker::new(100, KindMask::none().with(Source::Gauge));
+        tracker.sweep(50, &[&channel]);
+        tracker.sweep(200, &[&channel]);
+        assert_eq!(channel.reading(), 7);
+    }
+
+    #[test]
+    fn prunes_entries_for_channels_no_longer_swept() {
+        let stat_a = TestStatistic {
+            name: "a",
+            source: Source::Gauge,
+        };
+        let stat_b = TestStatistic {
+            name: "b",
+            source: Source::Gauge,
+        };
+        let a = Channel::<AtomicU64>::new(&stat_a, None);
+        let b = Channel::<AtomicU64>::new(&stat_b, None);
+        a.record_gauge(0, 1);
+        b.record_gauge(0, 1);
+
+        let tracker = RecencyTracker::new(100, KindMask::all());
+        tracker.sweep(0, &[&a, &b]);
+        assert_eq!(tracker.entries.lock().unwrap().len(), 2);
+
+        // "b" is no longer part of the registry's channel list
+        tracker.sweep(50, &[&a]);
+        assert_eq!(
+            tracker.entries.lock().unwrap().len(),
+            1,
+            "entry for the dropped channel 'b' should be pruned"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2019-2020 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A DDSketch-style relative-error quantile sketch.
+///
+/// Unlike `Histogram<T>`, whose buckets are a fixed set chosen up front,
+/// `Sketch` buckets logarithmically so that any quantile is guaranteed to
+/// be within a configurable relative error `alpha` of the true value,
+/// regardless of the value's magnitude. Sketches are cheaply mergeable,
+/// which makes them a good fit for per-thread or per-shard aggregation.
+pub struct Sketch {
+    gamma: f64,
+    buckets: Mutex<HashMap<i64, u64>>,
+}
+
+impl Sketch {
+    /// Creates a new sketch that guarantees a relative error of `alpha`
+    /// on any quantile, e.g. `alpha = 0.01` for 1% relative error.
+    pub fn new(alpha: f64) -> Self {
+        let gamma = (1.0 + alpha) / (1.0 - alpha);
+        Self {
+            gamma,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn bucket_index(&self, value: u64) -> i64 {
+        if value == 0 {
+            return i64::MIN;
+        }
+        ((value as f64).ln() / self.gamma.ln()).ceil() as i64
+    }
+
+    /// Records `count` occurrences of `value`.
+    pub fn increment(&self, value: u64, count: u64) {
+        let index = self.bucket_index(value);
+        let mut buckets = self.buckets.lock().unwrap();
+        *buckets.entry(index).or_insert(0) += count;
+    }
+
+    /// Estimates the value at quantile `q` (0.0 to 1.0), within the
+    /// configured relative error. Returns `None` if the sketch has no
+    /// recorded values.
+    pub fn percentile(&self, q: f64) -> Option<u64> {
+        let buckets = self.buckets.lock().unwrap();
+        quantile(self.gamma, &buckets, q)
+    }
+
+    /// Returns a snapshot of the current bucket index -> count map, e.g.
+    /// so it can be diffed against an earlier snapshot to compute a
+    /// windowed quantile (see `snapshot::SnapshotBuffer::percentile_over_window`).
+    pub fn buckets(&self) -> HashMap<i64, u64> {
+        self.buckets.lock().unwrap().clone()
+    }
+
+    /// The `gamma` growth factor derived from this sketch's `alpha`,
+    /// needed to interpret a bucket map returned by `buckets()` (e.g. for
+    /// a diffed, windowed bucket map from two snapshots).
+    pub fn gamma(&self) -> f64 {
+        self.gamma
+    }
+
+    /// Merges the bucket counts of `other` into this sketch, summing
+    /// counts for matching bucket indices. Both sketches must have been
+    /// created with the same `alpha`.
+    ///
+    /// Clones `other`'s buckets before taking `self`'s lock, rather than
+    /// holding both locks at once, so that two threads concurrently
+    /// running `a.merge(&b)` and `b.merge(&a)` can't deadlock on opposite
+    /// lock orders.
+    pub fn merge(&self, other: &Sketch) {
+        let other_buckets = other.buckets.lock().unwrap().clone();
+        let mut buckets = self.buckets.lock().unwrap();
+        for (index, count) in other_buckets.iter() {
+            *buckets.entry(*index).or_insert(0) += count;
+        }
+    }
+
+    /// Clears all recorded values.
+    pub fn clear(&self) {
+        self.buckets.lock().unwrap().clear();
+    }
+}
+
+/// Estimates the value represented by bucket `index` for the given
+/// `gamma`, within the sketch's relative error.
+pub fn estimate(gamma: f64, index: i64) -> u64 {
+    if index == i64::MIN {
+        return 0;
+    }
+    (2.0 * gamma.powi(index as i32) / (gamma + 1.0)).round() as u64
+}
+
+/// Estimates the value at quantile `q` (0.0 to 1.0) from a raw bucket
+/// index -> count map, without needing a `Sketch` instance. Used to
+/// answer quantiles over a diffed bucket map (e.g. a windowed quantile
+/// computed from two snapshots of `Sketch::buckets()`).
+pub fn quantile(gamma: f64, buckets: &HashMap<i64, u64>, q: f64) -> Option<u64> {
+    let total: u64 = buckets.values().sum();
+    if total == 0 {
+        return None;
+    }
+    let target = (q * total as f64).ceil() as u64;
+    let mut indices: Vec<&i64> = buckets.keys().collect();
+    indices.sort_unstable();
+    let mut accumulated = 0;
+    for &index in indices {
+        accumulated += buckets[&index];
+        if accumulated >= target {
+            return Some(estimate(gamma, index));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_is_within_relative_error() {
+        let sketch = Sketch::new(0.01);
+        for value in 1..=1000u64 {
+            sketch.increment(value, 1);
+        }
+        let p50 = sketch.percentile(0.5).unwrap();
+        assert!(
+            (p50 as f64 - 500.0).abs() / 500.0 < 0.01,
+            "p50 {} not within 1% of 500",
+            p50
+        );
+    }
+
+    #[test]
+    fn percentile_of_empty_sketch_is_none() {
+        let sketch = Sketch::new(0.01);
+        assert_eq!(sketch.percentile(0.5), None);
+    }
+
+    #[test]
+    fn merge_combines_bucket_counts() {
+        let a = Sketch::new(0.01);
+        let b = Sketch::new(0.01);
+        for _ in 0..50 {
+            a.increment(100, 1);
+        }
+        for _ in 0..50 {
+            b.increment(100, 1);
+        }
+        a.merge(&b);
+        assert_eq!(a.buckets().values().sum::<u64>(), 100);
+        let p50 = a.percentile(0.5).unwrap();
+        assert!((p50 as f64 - 100.0).abs() / 100.0 < 0.01);
+    }
+
+    #[test]
+    fn merge_does_not_deadlock_under_concurrent_cross_merge() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let a = Arc::new(Sketch::new(0.01));
+        let b = Arc::new(Sketch::new(0.01));
+        a.increment(10, 1);
+        b.increment(20, 1);
+
+        // counts roughly double each round a cross-merge observes the
+        // other sketch's latest state, so keep the round count well clear
+        // of overflowing a u64 bucket count
+        let (a1, b1) = (a.clone(), b.clone());
+        let t1 = thread::spawn(move || {
+            for _ in 0..20 {
+                a1.merge(&b1);
+            }
+        });
+        let (a2, b2) = (a.clone(), b.clone());
+        let t2 = thread::spawn(move || {
+            for _ in 0..20 {
+                b2.merge(&a2);
+            }
+        });
+        t1.join().unwrap();
+        t2.join().unwrap();
+    }
+}
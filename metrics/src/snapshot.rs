@@ -0,0 +1,245 @@
+// Copyright 2019-2020 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::*;
+
+use std::collections::{HashMap, VecDeque};
+
+/// A single point-in-time capture of every channel's `reading()` and, for
+/// channels backed by a `Sketch`, its bucket counts, keyed by channel
+/// name.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    time: u64,
+    readings: HashMap<String, u64>,
+    sketches: HashMap<String, (f64, HashMap<i64, u64>)>,
+}
+
+impl Snapshot {
+    /// Captures a new snapshot at `time` (nanoseconds) from a map of
+    /// channel name to reading.
+    pub fn new(time: u64, readings: HashMap<String, u64>) -> Self {
+        Self {
+            time,
+            readings,
+            sketches: HashMap::new(),
+        }
+    }
+
+    /// Captures a new snapshot at `time` (nanoseconds) of every channel in
+    /// `channels`, including `Sketch` bucket state for channels that have
+    /// one, so that `SnapshotBuffer::percentile_over_window` can later
+    /// report percentiles relative to a rolling window rather than since
+    /// the channel was last reset.
+    pub fn capture<T>(time: u64, channels: &[(&str, &Channel<T>)]) -> Self
+    where
+        T: Unsigned + SaturatingArithmetic + Default + FetchCompareStore,
+        <T as Atomic>::Primitive: Default + PartialEq + Copy + From<u8>,
+        u64: From<<T as Atomic>::Primitive>,
+    {
+        let mut readings = HashMap::new();
+        let mut sketches = HashMap::new();
+        for (name, channel) in channels {
+            readings.insert((*name).to_string(), channel.reading());
+            if let Some(buckets) = channel.sketch_buckets() {
+                sketches.insert((*name).to_string(), buckets);
+            }
+        }
+        Self {
+            time,
+            readings,
+            sketches,
+        }
+    }
+
+    /// The time, in nanoseconds, at which this snapshot was captured.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// The reading recorded for `name` in this snapshot, if any.
+    pub fn reading(&self, name: &str) -> Option<u64> {
+        self.readings.get(name).copied()
+    }
+
+    /// The `Sketch` bucket counts (and their `gamma`) recorded for `name`
+    /// in this snapshot, if any.
+    pub fn sketch_buckets(&self, name: &str) -> Option<&(f64, HashMap<i64, u64>)> {
+        self.sketches.get(name)
+    }
+}
+
+/// Retains the last `capacity` `Snapshot`s and answers windowed rate
+/// queries by diffing the newest snapshot against the most recent retained
+/// snapshot that is at least as old as the requested window.
+///
+/// `delta` and `percentile_over_window` each take the window to query as
+/// an argument, so callers are free to query any window their retained
+/// history covers rather than registering one up front.
+pub struct SnapshotBuffer {
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    /// Creates an empty buffer retaining up to `capacity` snapshots.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Pushes a new snapshot, evicting the oldest retained snapshot once
+    /// `capacity` is exceeded.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Computes the rate-per-second for channel `name` over `window`
+    /// nanoseconds, comparing the newest snapshot against the most recent
+    /// retained snapshot that is at least `window` old.
+    ///
+    /// Returns `None` if there are not yet enough retained snapshots to
+    /// cover the window, or if `name` is missing from either snapshot.
+    pub fn delta(&self, name: &str, window: u64) -> Option<f64> {
+        let newest = self.snapshots.back()?;
+        let target_time = newest.time().checked_sub(window)?;
+        // scan from the newest end so we find the closest match to the
+        // requested window, not the oldest snapshot in the whole buffer
+        let oldest = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.time() <= target_time)?;
+
+        let newest_value = newest.reading(name)?;
+        let oldest_value = oldest.reading(name)?;
+        let elapsed = newest.time().wrapping_sub(oldest.time());
+        if elapsed == 0 {
+            return None;
+        }
+        let delta_value = newest_value.wrapping_sub(oldest_value);
+        Some((delta_value as f64 / elapsed as f64) * 1_000_000_000.0)
+    }
+
+    /// Estimates the value at quantile `q` (0.0 to 1.0) for channel `name`
+    /// over the last `window` nanoseconds, by diffing the `Sketch` bucket
+    /// counts of the newest snapshot against the most recent retained
+    /// snapshot that is at least `window` old.
+    ///
+    /// Returns `None` if there are not yet enough retained snapshots to
+    /// cover the window, or if `name` was not captured with a `Sketch`.
+    pub fn percentile_over_window(&self, name: &str, window: u64, q: f64) -> Option<u64> {
+        let newest = self.snapshots.back()?;
+        let target_time = newest.time().checked_sub(window)?;
+        let oldest = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.time() <= target_time)?;
+
+        let (gamma, newest_buckets) = newest.sketch_buckets(name)?;
+        let diffed = match oldest.sketch_buckets(name) {
+            Some((_, oldest_buckets)) => {
+                let mut diffed = HashMap::new();
+                for (index, count) in newest_buckets {
+                    let prior = oldest_buckets.get(index).copied().unwrap_or(0);
+                    diffed.insert(*index, count.saturating_sub(prior));
+                }
+                diffed
+            }
+            None => newest_buckets.clone(),
+        };
+        crate::sketch::quantile(*gamma, &diffed, q)
+    }
+
+    /// Delta + zigzag + varint compresses every retained reading for
+    /// channel `name`, oldest first, for compact storage or transmission
+    /// of the reading history (see `streaming::compress`).
+    pub fn compressed_readings(&self, name: &str) -> Vec<u8> {
+        let readings: Vec<u64> = self
+            .snapshots
+            .iter()
+            .filter_map(|snapshot| snapshot.reading(name))
+            .collect();
+        crate::streaming::compress(&readings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_reading(buffer: &mut SnapshotBuffer, time: u64, name: &str, value: u64) {
+        let mut readings = HashMap::new();
+        readings.insert(name.to_string(), value);
+        buffer.push(Snapshot::new(time, readings));
+    }
+
+    #[test]
+    fn delta_matches_closest_snapshot_to_the_window() {
+        let mut buffer = SnapshotBuffer::new(10);
+        for t in (0..=900).step_by(100) {
+            push_reading(&mut buffer, t, "requests", t);
+        }
+        // the snapshot closest to (but not past) 900 - 150 = 750 is t=700
+        let rate = buffer.delta("requests", 150).unwrap();
+        let expected = (900 - 700) as f64 / (900 - 700) as f64 * 1_000_000_000.0;
+        assert_eq!(rate, expected);
+    }
+
+    #[test]
+    fn delta_is_none_without_enough_history() {
+        let mut buffer = SnapshotBuffer::new(10);
+        push_reading(&mut buffer, 0, "requests", 0);
+        assert_eq!(buffer.delta("requests", 100), None);
+    }
+
+    #[test]
+    fn percentile_over_window_diffs_bucket_counts() {
+        let gamma = 1.02_f64;
+        let index = ((100f64).ln() / gamma.ln()).ceil() as i64;
+
+        let mut buffer = SnapshotBuffer::new(10);
+        let mut oldest_buckets = HashMap::new();
+        oldest_buckets.insert(index, 5u64);
+        let mut oldest_sketches = HashMap::new();
+        oldest_sketches.insert("latency".to_string(), (gamma, oldest_buckets));
+        buffer.push(Snapshot {
+            time: 0,
+            readings: HashMap::new(),
+            sketches: oldest_sketches,
+        });
+
+        let mut newest_buckets = HashMap::new();
+        newest_buckets.insert(index, 25u64);
+        let mut newest_sketches = HashMap::new();
+        newest_sketches.insert("latency".to_string(), (gamma, newest_buckets));
+        buffer.push(Snapshot {
+            time: 100,
+            readings: HashMap::new(),
+            sketches: newest_sketches,
+        });
+
+        let p50 = buffer.percentile_over_window("latency", 100, 0.5).unwrap();
+        assert_eq!(p50, crate::sketch::estimate(gamma, index));
+    }
+
+    #[test]
+    fn compressed_readings_round_trips() {
+        let mut buffer = SnapshotBuffer::new(10);
+        push_reading(&mut buffer, 0, "requests", 100);
+        push_reading(&mut buffer, 100, "requests", 500);
+
+        let compressed = buffer.compressed_readings("requests");
+        assert_eq!(crate::streaming::decompress(&compressed), vec![100, 500]);
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2019-2020 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Delta + zigzag + variable-byte encoding for `u64` time series.
+//!
+//! Each value is encoded as the delta from its predecessor (the first
+//! value is encoded as-is), the signed delta is zigzag-mapped to an
+//! unsigned value, and the result is varint-encoded 7 bits per byte. This
+//! compresses monotonic counters, whose deltas are small and repetitive,
+//! down to a small fraction of their raw `u64` size.
+
+/// Compresses a slice of `u64` readings into a delta + zigzag + varint
+/// encoded byte stream.
+pub fn compress(values: &[u64]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(values.len());
+    let mut previous: u64 = 0;
+    for &value in values {
+        let delta = value.wrapping_sub(previous) as i64;
+        let zigzag = ((delta << 1) ^ (delta >> 63)) as u64;
+        write_varint(zigzag, &mut out);
+        previous = value;
+    }
+    out
+}
+
+/// Reverses `compress`, recovering the original `u64` readings.
+pub fn decompress(bytes: &[u8]) -> Vec<u64> {
+    let mut out = Vec::new();
+    let mut previous: u64 = 0;
+    let mut cursor = 0;
+    while cursor < bytes.len() {
+        let (zigzag, consumed) = read_varint(&bytes[cursor..]);
+        cursor += consumed;
+        let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+        previous = previous.wrapping_add(delta as u64);
+        out.push(previous);
+    }
+    out
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn read_varint(bytes: &[u8]) -> (u64, usize) {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    let mut consumed = 0;
+    for &byte in bytes {
+        consumed += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    (value, consumed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_simple_sequence() {
+        let values = vec![10, 12, 12, 15, 9, 0];
+        assert_eq!(decompress(&compress(&values)), values);
+    }
+
+    #[test]
+    fn round_trips_monotonic_counter() {
+        let values: Vec<u64> = (0..1000).map(|i| i * 7).collect();
+        assert_eq!(decompress(&compress(&values)), values);
+    }
+
+    #[test]
+    fn round_trips_u64_max() {
+        let values = vec![0, u64::MAX, 0, u64::MAX];
+        assert_eq!(decompress(&compress(&values)), values);
+    }
+
+    #[test]
+    fn round_trips_wrapping_counter() {
+        let values = vec![u64::MAX - 1, u64::MAX, 0, 1, 2];
+        assert_eq!(decompress(&compress(&values)), values);
+    }
+
+    #[test]
+    fn empty_round_trips_to_empty() {
+        let values: Vec<u64> = Vec::new();
+        assert_eq!(decompress(&compress(&values)), values);
+    }
+}